@@ -0,0 +1,231 @@
+//! Network gateways that let `joku serve` act as a persistent bridge to a Roku device.
+//!
+//! Each gateway wraps a shared [`RokuClient`] and forwards decoded [`RokuCommand`]s
+//! through [`Router::dispatch`], so the console, HTTP, and WebSocket front-ends all
+//! reach the device over a single code path.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::{bail, Result};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    response::IntoResponse,
+    routing::{get, post},
+};
+use futures_util::{SinkExt, StreamExt};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+use structopt::StructOpt;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::TcpListener,
+    signal,
+};
+use tracing::{error, info};
+
+use crate::roku::{LaunchParams, RokuClient, RokuCommand};
+
+/// Which gateways to expose and where to bind them.
+#[derive(Debug, StructOpt, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ServeParams {
+    /// Address for the HTTP gateway (REST keypress/launch/query endpoints).
+    #[structopt(long, default_value = "127.0.0.1:8060")]
+    http: SocketAddr,
+    /// Address for the WebSocket gateway; accepts JSON-encoded `RokuCommand` frames.
+    #[structopt(long, default_value = "127.0.0.1:8061")]
+    websocket: SocketAddr,
+    /// Also read newline-delimited JSON commands from stdin.
+    #[structopt(long)]
+    console: bool,
+}
+
+/// The shared dispatch surface used by every gateway.
+#[derive(Clone)]
+pub struct Router {
+    client: Arc<RokuClient>,
+}
+
+impl Router {
+    pub fn new(client: Arc<RokuClient>) -> Self {
+        Self { client }
+    }
+
+    /// Forwards a single decoded command to the device, returning a typed JSON result.
+    ///
+    /// Queries come back as parsed structs; action commands report transport success.
+    pub async fn dispatch(&self, command: RokuCommand) -> Result<serde_json::Value> {
+        match command {
+            RokuCommand::DeviceInfo => Ok(serde_json::to_value(self.client.device_info().await?)?),
+            RokuCommand::ListApps => Ok(serde_json::to_value(self.client.apps().await?)?),
+            // Not device commands: each resolves to an empty `RokuCommand::path()`, so
+            // forwarding them through `RokuClient::send` would just hit the device's
+            // root URL. Reject them instead of silently issuing a pointless request.
+            meta @ (RokuCommand::Discover
+            | RokuCommand::Serve(_)
+            | RokuCommand::Watch(_)
+            | RokuCommand::Devices(_)
+            | RokuCommand::Run(_)) => {
+                bail!("{meta:?} is not a device command and can't be sent through a gateway")
+            }
+            other => {
+                let method = other.method();
+                self.client.send(other, method).await?;
+
+                Ok(json!({ "ok": true }))
+            }
+        }
+    }
+}
+
+/// Starts the selected gateways and returns once all of them have stopped.
+///
+/// The HTTP and WebSocket gateways stop on `ctrl-c`; the console gateway does not
+/// watch for `ctrl-c` and runs until stdin is closed.
+pub async fn serve(params: ServeParams, client: RokuClient) -> Result<()> {
+    let console_enabled = params.console;
+    let router = Router::new(Arc::new(client));
+
+    let http = tokio::spawn(serve_http(router.clone(), params.http));
+    let websocket = tokio::spawn(serve_websocket(router.clone(), params.websocket));
+    // Spawned unconditionally so it can be joined alongside the other gateways; it's a
+    // no-op future when `--console` wasn't passed.
+    let console = tokio::spawn(async move {
+        if console_enabled {
+            serve_console(router).await
+        } else {
+            Ok(())
+        }
+    });
+
+    let (http, websocket, console) = tokio::try_join!(http, websocket, console)?;
+    http?;
+    websocket?;
+    console?;
+
+    Ok(())
+}
+
+/// Resolves once the process receives `ctrl-c`, used to drain in-flight requests.
+async fn shutdown_signal() {
+    let _ = signal::ctrl_c().await;
+    info!("received ctrl-c, shutting down gateways");
+}
+
+/// REST gateway: `POST /keypress/{key}`, `POST /launch/{app}`, `GET /device-info`, `GET /apps`.
+async fn serve_http(router: Router, addr: SocketAddr) -> Result<()> {
+    let app = axum::Router::new()
+        .route("/keypress/:key", post(http_keypress))
+        .route("/launch/:app", post(http_launch))
+        .route("/device-info", get(http_device_info))
+        .route("/apps", get(http_apps))
+        .with_state(router);
+
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "http gateway listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    Ok(())
+}
+
+/// WebSocket gateway: a live session forwarding a stream of JSON `RokuCommand` frames.
+async fn serve_websocket(router: Router, addr: SocketAddr) -> Result<()> {
+    let app = axum::Router::new()
+        .route("/", get(ws_upgrade))
+        .with_state(router);
+
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "websocket gateway listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    Ok(())
+}
+
+/// Console gateway: newline-delimited JSON `RokuCommand`s read from stdin.
+async fn serve_console(router: Router) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<RokuCommand>(line) {
+            Ok(command) => match router.dispatch(command).await {
+                Ok(body) => println!("{body}"),
+                Err(e) => error!(%e, "dispatch failed"),
+            },
+            Err(e) => error!(%e, "invalid command"),
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct LaunchQuery {
+    link: Option<String>,
+}
+
+async fn http_keypress(State(router): State<Router>, Path(key): Path<String>) -> impl IntoResponse {
+    match RokuCommand::from_key(&key) {
+        Ok(command) => forward(&router, command).await,
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+async fn http_launch(
+    State(router): State<Router>,
+    Path(app): Path<String>,
+    Query(query): Query<LaunchQuery>,
+) -> impl IntoResponse {
+    forward(&router, RokuCommand::Launch(LaunchParams::new(app, query.link))).await
+}
+
+async fn http_device_info(State(router): State<Router>) -> impl IntoResponse {
+    forward(&router, RokuCommand::DeviceInfo).await
+}
+
+async fn http_apps(State(router): State<Router>) -> impl IntoResponse {
+    forward(&router, RokuCommand::ListApps).await
+}
+
+/// Dispatches a command and maps the outcome onto an HTTP status + body.
+async fn forward(router: &Router, command: RokuCommand) -> (StatusCode, String) {
+    match router.dispatch(command).await {
+        Ok(body) => (StatusCode::OK, body.to_string()),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()),
+    }
+}
+
+async fn ws_upgrade(State(router): State<Router>, ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| ws_session(router, socket))
+}
+
+/// Forwards each text frame as a `RokuCommand`, replying with a small JSON status.
+async fn ws_session(router: Router, mut socket: WebSocket) {
+    while let Some(Ok(message)) = socket.next().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+
+        let reply = match serde_json::from_str::<RokuCommand>(&text) {
+            Ok(command) => match router.dispatch(command).await {
+                Ok(body) => json!({ "ok": true, "body": body }),
+                Err(e) => json!({ "ok": false, "error": e.to_string() }),
+            },
+            Err(e) => json!({ "ok": false, "error": e.to_string() }),
+        };
+
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
+        }
+    }
+}
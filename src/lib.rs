@@ -1,16 +1,11 @@
 use std::{env, path::PathBuf};
 
 use anyhow::Result;
-use reqwest::Url;
-use roku::RokuCommand;
 
+pub mod gateway;
 pub mod roku;
-
-fn urlify(base: &Url, command: &RokuCommand) -> anyhow::Result<Url> {
-    let url = base.join(&command.to_string())?;
-
-    Ok(url)
-}
+pub mod script;
+pub mod watch;
 
 /// Where the `config.toml` file is located
 pub fn config_path() -> Result<PathBuf> {
@@ -20,9 +15,3 @@ pub fn config_path() -> Result<PathBuf> {
 
     Ok(path)
 }
-
-fn config_file() -> Result<PathBuf> {
-    let file = config_path()?.join("config.toml");
-
-    Ok(file)
-}
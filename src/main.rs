@@ -3,18 +3,32 @@
 //! I named the command `joku` because my name starts with a J. That's really it.
 //!
 //! See https://developer.roku.com/docs/developer-program/debugging/external-control-api.md#keypress-key-values
-use std::fs;
+use std::{fs, path::PathBuf};
 
 use anyhow::Result;
-use inquire::Select;
+use inquire::{Select, Text};
 use joku::{
-    config_path,
-    roku::{self, get_roku_apps, App, Config, RokuClient, RokuCommand, RokuDevice},
+    config_path, gateway,
+    roku::{
+        self, get_roku_apps, App, Config, DevicesCommand, RokuClient, RokuCommand, RokuDevice,
+        SavedDevice,
+    },
+    script, watch,
 };
-use reqwest::{Method, Url};
 use structopt::StructOpt;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+/// The top-level CLI: a subcommand plus the global device selector.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "joku")]
+struct Cli {
+    /// Target device alias; defaults to the configured default device.
+    #[structopt(long, short, global = true)]
+    device: Option<String>,
+    #[structopt(subcommand)]
+    command: RokuCommand,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::registry()
@@ -22,24 +36,37 @@ async fn main() -> Result<()> {
         .with(EnvFilter::from_default_env())
         .init();
 
-    let command = RokuCommand::from_args();
+    let cli = Cli::from_args();
+    let path = config_path()?.join("config.toml");
 
-    match command {
+    match cli.command {
         RokuCommand::Discover => {
             println!("Searching for Roku devices...");
             let devices = roku::get_roku_devices().await?;
-            let ans = Select::new("Select your primary Roku device.", devices).prompt()?;
+            let ans = Select::new("Select a Roku device.", devices).prompt()?;
 
-            let url = Url::parse(format!("http://{}", ans.addr).as_str())?;
+            let url = reqwest::Url::parse(format!("http://{}", ans.addr).as_str())?;
             let apps: Vec<App> = get_roku_apps(&url).await?;
 
-            write_to_config(ans, apps)?;
+            add_discovered_device(ans, apps)?;
         }
-        _ => {
-            let path = config_path()?.join("config.toml");
-
-            let _resp = RokuClient::try_from_config(&path)?
-                .send(command, Method::POST)
+        RokuCommand::Devices(cmd) => manage_devices(cmd)?,
+        RokuCommand::Serve(params) => {
+            let client = RokuClient::try_from_config(&path, cli.device.as_deref())?;
+            gateway::serve(params, client).await?;
+        }
+        RokuCommand::Watch(params) => {
+            let client = RokuClient::try_from_config(&path, cli.device.as_deref())?;
+            watch::run(params, client).await?;
+        }
+        RokuCommand::Run(params) => {
+            let client = RokuClient::try_from_config(&path, cli.device.as_deref())?;
+            script::run(params, client).await?;
+        }
+        command => {
+            let method = command.method();
+            RokuClient::try_from_config(&path, cli.device.as_deref())?
+                .send(command, method)
                 .await?;
         }
     }
@@ -47,16 +74,90 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-/// Writes the `RokuDevice` to the `config.toml` file.
-/// This will include the name and socket address.
-fn write_to_config(device: RokuDevice, apps: Vec<App>) -> Result<()> {
-    let path = config_path()?;
-    fs::create_dir_all(path.clone())?;
+/// Appends a newly discovered device to the config, prompting for an alias.
+///
+/// Existing devices are preserved; the first device added becomes the default.
+fn add_discovered_device(device: RokuDevice, apps: Vec<App>) -> Result<()> {
+    let file = config_path()?.join("config.toml");
+    fs::create_dir_all(config_path()?)?;
+
+    let alias = Text::new("Alias for this device:")
+        .with_default(&device.name)
+        .prompt()?;
+
+    let mut config = load_config(&file).unwrap_or_default();
+    // Replace any device already saved under this alias, otherwise append.
+    config.devices.retain(|d| d.alias != alias);
+    config.devices.push(SavedDevice { alias: alias.clone(), device, apps });
+    if config.default.is_none() {
+        config.default = Some(alias);
+    }
+
+    write_config(&file, &config)
+}
+
+/// Handles the `devices` subcommand (list/add/remove/set-default).
+fn manage_devices(cmd: DevicesCommand) -> Result<()> {
+    let file = config_path()?.join("config.toml");
 
-    let file = path.join("config.toml");
+    match cmd {
+        DevicesCommand::List => {
+            let config = load_config(&file)?;
+            for device in &config.devices {
+                let marker = if config.default.as_deref() == Some(&device.alias) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{marker} {} ({})", device.alias, device.device.addr);
+            }
+        }
+        DevicesCommand::Add { alias, addr } => {
+            fs::create_dir_all(config_path()?)?;
+            let mut config = load_config(&file).unwrap_or_default();
+            config.devices.retain(|d| d.alias != alias);
+            config.devices.push(SavedDevice {
+                device: RokuDevice { name: alias.clone(), addr },
+                alias: alias.clone(),
+                apps: vec![],
+            });
+            if config.default.is_none() {
+                config.default = Some(alias);
+            }
+            write_config(&file, &config)?;
+        }
+        DevicesCommand::Remove { alias } => {
+            let mut config = load_config(&file)?;
+            config.devices.retain(|d| d.alias != alias);
+            // If the default pointed at the removed device, move it to the first remaining one.
+            if config.default.as_deref() == Some(&alias) {
+                config.default = config.devices.first().map(|d| d.alias.clone());
+            }
+            write_config(&file, &config)?;
+        }
+        DevicesCommand::SetDefault { alias } => {
+            let mut config = load_config(&file)?;
+            if !config.devices.iter().any(|d| d.alias == alias) {
+                anyhow::bail!("Unknown device: {alias}");
+            }
+            config.default = Some(alias);
+            write_config(&file, &config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads and parses the `config.toml` file.
+fn load_config(file: &PathBuf) -> Result<Config> {
+    let config = basic_toml::from_slice(&fs::read(file)?)?;
+
+    Ok(config)
+}
 
-    let toml = basic_toml::to_string(&Config { device, apps })?;
-    fs::write(file, toml)?;
+/// Serializes and writes the config back to `config.toml`.
+fn write_config(file: &PathBuf, config: &Config) -> Result<()> {
+    fs::write(file, basic_toml::to_string(config)?)?;
 
     Ok(())
 }
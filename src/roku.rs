@@ -3,17 +3,16 @@
 use std::{collections::HashMap, fmt::Display, fs, net::SocketAddr, path::PathBuf, time::Duration};
 
 use anyhow::{anyhow, bail, Result};
-use basic_toml::from_str as toml_from_str;
 use futures_util::{stream, StreamExt};
-use quick_xml::{events::Event, Reader};
-use reqwest::{Client, Method, Response, Url};
-use serde::{Deserialize, Serialize};
+use reqwest::{Client, Method, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_xml_rs::from_str;
 use ssdp_client::SearchTarget;
 use structopt::StructOpt;
+use tokio::time::sleep;
 use tracing::{debug, error};
 
-use crate::{config_file, urlify};
+use crate::{gateway::ServeParams, script::RunParams, watch::WatchParams};
 
 /// Provides the subcommands to excute the [`External Control API`](https://developer.roku.com/docs/developer-program/debugging/external-control-api.md#keypress-key-values)
 #[derive(Debug, StructOpt)]
@@ -43,6 +42,65 @@ pub enum RokuCommand {
     Launch(LaunchParams),
     DeviceInfo,
     ListApps,
+    /// Types free text into the currently focused input field, one character at a time.
+    Type { text: String },
+    /// Presses and holds a key without releasing it (ECP `keydown`).
+    KeyDown { key: String },
+    /// Releases a previously held key (ECP `keyup`).
+    KeyUp { key: String },
+    /// Runs joku as a persistent control server fronting the configured device.
+    Serve(ServeParams),
+    /// Polls the device and emits state-change events as newline-delimited JSON.
+    Watch(WatchParams),
+    /// Manage the list of saved Roku devices.
+    Devices(DevicesCommand),
+    /// Run a TOML script of commands, sleeps, and waits against a device.
+    Run(RunParams),
+}
+
+/// Subcommands for managing the saved device list.
+#[derive(Debug, StructOpt, Serialize, Deserialize, Clone)]
+pub enum DevicesCommand {
+    /// List saved devices; the default is marked with `*`.
+    List,
+    /// Add a device by alias and socket address.
+    Add { alias: String, addr: SocketAddr },
+    /// Remove a device by alias.
+    Remove { alias: String },
+    /// Set the default device by alias.
+    SetDefault { alias: String },
+}
+
+impl RokuCommand {
+    /// The HTTP method the Roku ECP expects for this command.
+    pub fn method(&self) -> Method {
+        match self {
+            RokuCommand::DeviceInfo | RokuCommand::ListApps => Method::GET,
+            _ => Method::POST,
+        }
+    }
+
+    /// Resolves an ECP key name (e.g. `Home`, `VolumeUp`) to its command.
+    pub fn from_key(key: &str) -> Result<Self> {
+        let command = match key.to_lowercase().as_str() {
+            "home" => RokuCommand::Home,
+            "play" => RokuCommand::Play,
+            "pause" => RokuCommand::Pause,
+            "select" => RokuCommand::Select,
+            "left" => RokuCommand::Left,
+            "right" => RokuCommand::Right,
+            "down" => RokuCommand::Down,
+            "up" => RokuCommand::Up,
+            "back" => RokuCommand::Back,
+            "volumeup" => RokuCommand::VolumeUp,
+            "volumedown" => RokuCommand::VolumeDown,
+            "mute" => RokuCommand::Mute,
+            "poweroff" => RokuCommand::PowerOff,
+            _ => bail!("Unknown key: {key}"),
+        };
+
+        Ok(command)
+    }
 }
 
 /// The params for a search query, however this isn't working great!
@@ -67,62 +125,195 @@ pub struct SearchParams {
 #[derive(Debug, StructOpt, Serialize, Deserialize, Clone)]
 pub struct LaunchParams {
     app: String,
-    // TODO: It might be nice if this is optional, and we can just launch apps.
-    // In that case, we don't need a `RokuApp` variant for the app, since we don't care about
-    // parsing the link.
+    /// A public watch URL to deep-link into; resolved per app by the [`DeepLink`] registry.
     link: Option<String>,
+    /// Explicit ECP `contentId`, used when no `link` is given.
+    #[structopt(long)]
+    content_id: Option<String>,
+    /// Explicit ECP `mediaType` (e.g. `movie`, `series`).
+    #[structopt(long)]
+    media_type: Option<String>,
+    /// Arbitrary extra launch parameters as `key=value` (repeatable).
+    #[structopt(long = "param", parse(try_from_str = parse_param))]
+    params: Vec<(String, String)>,
 }
 
 impl LaunchParams {
-    fn path(&self) -> Result<String> {
-        // TODO: Separate fn maybe? `load_apps` or something?
-        let config = config_file()?;
-        let apps: Apps = toml_from_str(&fs::read_to_string(config)?)?;
+    /// Builds launch params from an app name and an optional deep-link URL.
+    pub fn new(app: String, link: Option<String>) -> Self {
+        Self { app, link, content_id: None, media_type: None, params: vec![] }
+    }
 
+    /// Resolves the `launch/{appId}` path (and query) for this launch against a
+    /// device's installed app catalog.
+    fn path(&self, apps: &[App]) -> Result<String> {
         let app = apps
-            .apps
-            .into_iter()
+            .iter()
             .find(|a| a.name.to_lowercase() == self.app.to_lowercase())
-            .ok_or(anyhow!("Unknown roku app"))?
-            .try_into()?;
-
-        let path = match app {
-            // TODO: Maintaining this for each app will be very annoying...
-            // Perhaps we should have a trait that `RokuApp` implements and move the parsing there.
-            RokuApp::YouTube(app_id) => match &self.link {
-                Some(url) => {
-                    if let Ok(url) = Url::parse(url) {
-                        let query: HashMap<_, _> = url.query_pairs().into_iter().collect();
-                        // Parse the ID out of the youtube link
-                        let id = query
-                            .get("v")
-                            .map(|v| v.to_string())
-                            .map(|content_id| format!("{app_id}?contentId={content_id}"));
-
-                        id
-                    } else {
-                        None
-                    }
-                }
-                None => Some(app_id),
+            .ok_or(anyhow!("Unknown roku app"))?;
+
+        // A registered resolver turns a public watch URL into the ECP launch query;
+        // otherwise fall back to whatever the user passed explicitly.
+        let launch = match (&self.link, deep_link_registry().into_iter().find(|d| d.matches(&app.name))) {
+            (Some(link), Some(resolver)) => resolver.resolve(&Url::parse(link)?)?,
+            (Some(_), None) => bail!(
+                "No deep-link resolver registered for {:?}; use --content-id/--media-type/--param instead of a link",
+                app.name
+            ),
+            (None, _) => LaunchPath {
+                content_id: self.content_id.clone(),
+                media_type: self.media_type.clone(),
+                params: self.params.clone(),
             },
+        };
+
+        let query = launch.query();
+        if query.is_empty() {
+            Ok(format!("launch/{}", app.id))
+        } else {
+            Ok(format!("launch/{}?{}", app.id, query))
         }
-        .ok_or(anyhow!("Invalid content identifier"))?;
+    }
+}
 
-        Ok(path)
+/// Parses a `key=value` launch parameter from the command line.
+fn parse_param(raw: &str) -> Result<(String, String)> {
+    let (key, value) = raw.split_once('=').ok_or(anyhow!("Expected key=value, got {raw}"))?;
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// The resolved ECP query for a `launch/{appId}` request.
+#[derive(Debug, Default, Clone)]
+pub struct LaunchPath {
+    content_id: Option<String>,
+    media_type: Option<String>,
+    params: Vec<(String, String)>,
+}
+
+impl LaunchPath {
+    /// Renders the query string portion of the launch URL (without a leading `?`).
+    fn query(&self) -> String {
+        let mut parts = vec![];
+        if let Some(content_id) = &self.content_id {
+            parts.push(format!("contentId={content_id}"));
+        }
+        if let Some(media_type) = &self.media_type {
+            parts.push(format!("mediaType={media_type}"));
+        }
+        for (key, value) in &self.params {
+            parts.push(format!("{key}={value}"));
+        }
+
+        parts.join("&")
     }
 }
 
-#[derive(Debug, Clone)]
-enum RokuApp {
-    /// The YouTube application with its application ID.
-    YouTube(String),
+/// Maps a public watch URL to an ECP deep link for a particular app.
+trait DeepLink {
+    /// Whether this resolver handles the given Roku app (by friendly name).
+    fn matches(&self, app_name: &str) -> bool;
+    /// Extracts the content identifier (and media type) from a public watch URL.
+    fn resolve(&self, link: &Url) -> Result<LaunchPath>;
 }
 
-impl Display for RokuCommand {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// The built-in resolvers, tried in order against the requested app's name.
+fn deep_link_registry() -> Vec<Box<dyn DeepLink>> {
+    vec![
+        Box::new(YouTube),
+        Box::new(Netflix),
+        Box::new(DisneyPlus),
+        Box::new(PrimeVideo),
+    ]
+}
+
+/// The last non-empty path segment of a URL, e.g. the title id in `/watch/80100172`.
+fn last_segment(link: &Url) -> Option<String> {
+    link.path_segments()?.filter(|s| !s.is_empty()).last().map(str::to_string)
+}
+
+struct YouTube;
+
+impl DeepLink for YouTube {
+    fn matches(&self, app_name: &str) -> bool {
+        app_name.eq_ignore_ascii_case("youtube")
+    }
+
+    fn resolve(&self, link: &Url) -> Result<LaunchPath> {
+        let query: HashMap<_, _> = link.query_pairs().collect();
+        let content_id = query.get("v").map(|v| v.to_string()).ok_or(anyhow!("No video id in URL"))?;
+
+        Ok(LaunchPath { content_id: Some(content_id), ..Default::default() })
+    }
+}
+
+struct Netflix;
+
+impl DeepLink for Netflix {
+    fn matches(&self, app_name: &str) -> bool {
+        app_name.eq_ignore_ascii_case("netflix")
+    }
+
+    fn resolve(&self, link: &Url) -> Result<LaunchPath> {
+        let content_id = last_segment(link).ok_or(anyhow!("No title id in URL"))?;
+        // Netflix distinguishes movies from series; default to movie when unknown.
+        let media_type = if link.path().contains("/series") { "series" } else { "movie" };
+
+        Ok(LaunchPath {
+            content_id: Some(content_id),
+            media_type: Some(media_type.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+struct DisneyPlus;
+
+impl DeepLink for DisneyPlus {
+    fn matches(&self, app_name: &str) -> bool {
+        let app_name = app_name.to_lowercase();
+        app_name.contains("disney")
+    }
+
+    fn resolve(&self, link: &Url) -> Result<LaunchPath> {
+        let content_id = last_segment(link).ok_or(anyhow!("No content id in URL"))?;
+
+        Ok(LaunchPath { content_id: Some(content_id), ..Default::default() })
+    }
+}
+
+struct PrimeVideo;
+
+impl DeepLink for PrimeVideo {
+    fn matches(&self, app_name: &str) -> bool {
+        let app_name = app_name.to_lowercase();
+        app_name.contains("prime video") || app_name.contains("amazon")
+    }
+
+    fn resolve(&self, link: &Url) -> Result<LaunchPath> {
+        let query: HashMap<_, _> = link.query_pairs().collect();
+        // Amazon carries the title in a `gti` query param, otherwise it's the last path segment.
+        let content_id = query
+            .get("gti")
+            .map(|v| v.to_string())
+            .or_else(|| last_segment(link))
+            .ok_or(anyhow!("No content id in URL"))?;
+
+        Ok(LaunchPath { content_id: Some(content_id), ..Default::default() })
+    }
+}
+
+impl RokuCommand {
+    /// Resolves the ECP path this command sends to, e.g. `keypress/Home` or
+    /// `query/device-info`.
+    ///
+    /// `Launch` can't be resolved here: its `launch/{appId}` path depends on the
+    /// target device's installed app catalog, which this method has no access to.
+    /// `RokuClient::send` special-cases `Launch` and resolves it via
+    /// [`LaunchParams::path`] before a command ever reaches this method.
+    fn path(&self) -> Result<String> {
         let key_cmd = "keypress";
-        let command = match self {
+        let path = match self {
             RokuCommand::Pause => format!("{key_cmd}/Pause"),
             RokuCommand::Home => format!("{key_cmd}/Home"),
             RokuCommand::Play => format!("{key_cmd}/Play"),
@@ -146,19 +337,37 @@ impl Display for RokuCommand {
                 format!("{base}?{qs}")
             }
             RokuCommand::DeviceInfo => "query/device-info".to_string(),
+            // The key name is percent-encoded so it can't smuggle a `/` (or `..`) into
+            // the request path, e.g. a `key` of `../query/device-info`.
+            RokuCommand::KeyDown { key } => format!("keydown/{}", percent_encode_segment(key)),
+            RokuCommand::KeyUp { key } => format!("keyup/{}", percent_encode_segment(key)),
             // TODO: Would be nice if this also took a callback to send a follow up command, like
             // select/pause/etc
-            RokuCommand::Launch(params) => match params.path() {
-                Ok(path) => {
-                    format!("launch/{}", path)
-                }
-                Err(e) => panic!("Bad launch params! {:?}", e),
-            },
+            RokuCommand::Launch(_) => {
+                bail!("Launch must be sent through RokuClient, which holds the device's app catalog")
+            }
             // Not a real Roku command, we're using this to discover Roku devices on the network.
             RokuCommand::Discover => "".to_string(),
             RokuCommand::ListApps => "query/apps".to_string(),
+            // Typed text expands into many `Lit_` keypresses; sent via `RokuClient::type_text`.
+            RokuCommand::Type { .. } => "".to_string(),
+            // Not real Roku commands; handled before a request is ever sent.
+            RokuCommand::Serve(_) => "".to_string(),
+            RokuCommand::Watch(_) => "".to_string(),
+            RokuCommand::Devices(_) => "".to_string(),
+            RokuCommand::Run(_) => "".to_string(),
         };
-        write!(f, "{command}")
+
+        Ok(path)
+    }
+}
+
+impl Display for RokuCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path() {
+            Ok(path) => write!(f, "{path}"),
+            Err(_) => Err(std::fmt::Error),
+        }
     }
 }
 
@@ -175,38 +384,132 @@ impl Display for RokuDevice {
     }
 }
 
-/// A representation of the config.toml file containing the name and socket address of the Roku device.
+/// A saved device: the discovered `RokuDevice`, a user-assigned alias, and the
+/// app catalog discovered on that device (used to resolve `Launch`).
 #[derive(Debug, Serialize, Deserialize)]
-pub struct Config {
+pub struct SavedDevice {
+    pub alias: String,
     pub device: RokuDevice,
+    #[serde(default)]
     pub apps: Vec<App>,
 }
 
+/// A representation of the config.toml file: the saved devices and the default alias.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub devices: Vec<SavedDevice>,
+}
+
+impl Config {
+    /// Resolves a device by alias, falling back to the default (or the first saved device).
+    pub fn resolve(&self, selector: Option<&str>) -> Result<&SavedDevice> {
+        match selector.or(self.default.as_deref()) {
+            Some(alias) => self
+                .devices
+                .iter()
+                .find(|d| d.alias == alias)
+                .ok_or(anyhow!("Unknown device: {alias}")),
+            None => self.devices.first().ok_or(anyhow!("No devices configured")),
+        }
+    }
+}
+
 /// Encapsulates sending commands to the Roku device
 pub struct RokuClient {
     base: Url,
+    /// The resolved device's installed app catalog, used to look up `Launch` targets.
+    apps: Vec<App>,
 }
 
 impl RokuClient {
     pub fn new(base: Url) -> Self {
-        Self { base }
+        Self { base, apps: vec![] }
     }
 
     /// Creates a new `RokuClient`.
     ///
-    /// This will read the device's address from the `config.toml`.
-    pub fn try_from_config(config: &PathBuf) -> Result<Self> {
+    /// This reads the device list from `config.toml` and resolves `selector` against
+    /// it, falling back to the configured default device.
+    pub fn try_from_config(config: &PathBuf, selector: Option<&str>) -> Result<Self> {
         let toml = fs::read(config)?;
         let config: Config = basic_toml::from_slice(&toml)?;
 
-        let url = Url::parse(format!("http://{}", &config.device.addr).as_str())?;
+        let device = config.resolve(selector)?;
+        let url = Url::parse(format!("http://{}", &device.device.addr).as_str())?;
+
+        Ok(Self { base: url, apps: device.apps.clone() })
+    }
+
+    /// Sends an action `RokuCommand` (keypress, launch, ...) to the device.
+    ///
+    /// Action commands have no meaningful body, so only transport success is reported.
+    /// For queries, prefer the typed [`device_info`](Self::device_info),
+    /// [`apps`](Self::apps), and [`active_app`](Self::active_app) methods.
+    pub async fn send(&self, command: RokuCommand, method: Method) -> Result<()> {
+        match command {
+            // Text entry fans out into one request per character, so it can't go through
+            // the single-URL `send_cmd` path.
+            RokuCommand::Type { text } => self.type_text(&text).await,
+            // Resolved against this client's device's app catalog, not a config-wide list.
+            RokuCommand::Launch(params) => {
+                let path = params.path(&self.apps)?;
+                send_to_path(&self.base, &path, method).await?;
+
+                Ok(())
+            }
+            other => {
+                send_cmd(other, &self.base, method).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Fetches and parses `query/device-info`.
+    pub async fn device_info(&self) -> Result<DeviceInfo> {
+        let body = send_cmd(RokuCommand::DeviceInfo, &self.base, Method::GET).await?;
+
+        decode(&body)
+    }
+
+    /// Fetches and parses `query/apps` into the installed app catalog.
+    pub async fn apps(&self) -> Result<Vec<App>> {
+        let body = send_cmd(RokuCommand::ListApps, &self.base, Method::GET).await?;
+
+        Ok(decode::<Apps>(&body)?.apps)
+    }
+
+    /// Fetches and parses `query/active-app` into the foreground app.
+    pub async fn active_app(&self) -> Result<App> {
+        let url = self.base.join("query/active-app")?;
+        let body = Client::new().get(url).send().await?.text().await?;
 
-        Ok(Self::new(url))
+        Ok(decode::<ActiveApp>(&body)?.app)
     }
 
-    /// Sends a `RokuCommand` to the Roku device.
-    pub async fn send(&self, command: RokuCommand, method: Method) -> Result<Response> {
-        send_cmd(command, &self.base, method).await
+    /// Types `text` into the focused field, sending one `Lit_` keypress per character.
+    ///
+    /// Characters are sent sequentially with a small inter-key delay so the device's
+    /// input handler keeps up; multi-byte UTF-8 characters are percent-encoded per byte.
+    pub async fn type_text(&self, text: &str) -> Result<()> {
+        let client = Client::new();
+
+        for ch in text.chars() {
+            let encoded: String = ch.to_string().bytes().map(percent_encode_byte).collect();
+            let url = self.base.join(&format!("keypress/Lit_{encoded}"))?;
+            debug!(?url, "Typing {:?}", ch);
+
+            let resp = client.request(Method::POST, url).send().await?;
+            if !resp.status().is_success() {
+                error!(?resp, "Request to Roku device failed");
+            }
+
+            sleep(INTER_KEY_DELAY).await;
+        }
+
+        Ok(())
     }
 
     pub fn base(&self) -> &Url {
@@ -214,15 +517,58 @@ impl RokuClient {
     }
 }
 
-// TODO: I hate this being `pub`. Should be just an internal type for parsing.
+/// How long to wait between keypresses when typing so the device keeps up.
+const INTER_KEY_DELAY: Duration = Duration::from_millis(50);
+
+/// Percent-encodes a single byte for a `Lit_` keypress, leaving unreserved bytes as-is
+/// (e.g. `a` -> `a`, space -> `%20`).
+fn percent_encode_byte(byte: u8) -> String {
+    if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+        (byte as char).to_string()
+    } else {
+        format!("%{byte:02X}")
+    }
+}
+
+/// Percent-encodes an ECP key name for use as a single path segment, so a caller
+/// can't smuggle a `/` (or `..`) into the request path, e.g. a `KeyDown` key of
+/// `../query/device-info`.
+fn percent_encode_segment(segment: &str) -> String {
+    segment.bytes().map(percent_encode_byte).collect()
+}
+
+/// Typed device facts parsed from `query/device-info`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    #[serde(rename = "friendly-device-name")]
+    pub friendly_name: String,
+    #[serde(rename = "model-name")]
+    pub model: String,
+    #[serde(rename = "serial-number")]
+    pub serial: String,
+    #[serde(rename = "power-mode")]
+    pub power_mode: String,
+    #[serde(rename = "network-type")]
+    pub network_type: String,
+    #[serde(rename = "software-version")]
+    pub software_version: String,
+}
+
+/// The `query/apps` response: the installed app catalog.
 #[derive(Debug, Deserialize)]
 pub struct Apps {
     #[serde(alias = "$value")]
     apps: Vec<App>,
 }
 
-// TODO: I hate this being `pub`. Should be just an internal type for parsing.
-#[derive(Debug, Serialize, Deserialize)]
+/// The `query/active-app` response wrapping the foreground app.
+#[derive(Debug, Deserialize)]
+struct ActiveApp {
+    app: App,
+}
+
+/// A single installed Roku app (channel).
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct App {
     id: String,
     r#type: String,
@@ -231,26 +577,18 @@ pub struct App {
     name: String,
 }
 
-impl TryFrom<App> for RokuApp {
-    type Error = anyhow::Error;
-
-    fn try_from(value: App) -> std::result::Result<Self, Self::Error> {
-        match value.name.to_lowercase().as_str() {
-            "youtube" => Ok(RokuApp::YouTube(value.id)),
-            _ => bail!("Unsupported app: {:?}", value),
-        }
-    }
+/// Decodes a Roku ECP XML response body into a typed value.
+///
+/// Every ECP query in this module is decoded here, so entity unescaping
+/// (`&quot;` and friends) and field extraction aren't duplicated per call site.
+fn decode<T: DeserializeOwned>(xml: &str) -> Result<T> {
+    Ok(from_str(xml)?)
 }
 
-// TOOD: Make this a method on `Apps`
 pub async fn get_roku_apps(base: &Url) -> Result<Vec<App>> {
-    let resp = send_cmd(RokuCommand::ListApps, base, Method::GET).await?;
+    let body = send_cmd(RokuCommand::ListApps, base, Method::GET).await?;
 
-    let body = resp.text().await?;
-
-    let apps: Apps = from_str(&body)?;
-
-    Ok(apps.apps)
+    Ok(decode::<Apps>(&body)?.apps)
 }
 
 /// Searches for all Roku devices on the network.
@@ -260,40 +598,28 @@ pub async fn get_roku_apps(base: &Url) -> Result<Vec<App>> {
 /// Maybe not `RokuClient`, but something...
 pub async fn get_roku_devices() -> Result<Vec<RokuDevice>> {
     let urls = get_roku_addr().await?;
-    let device_info_futs = urls
-        .iter()
-        .map(|url| async move { send_cmd(RokuCommand::DeviceInfo, url, Method::GET).await });
+    let device_info_futs = urls.iter().map(|url| send_device_info(url));
 
     let mut stream = stream::iter(device_info_futs).buffer_unordered(5);
 
     let mut devices = vec![];
-    // TODO: This is a lot of code to just grab a value out of the XML response.
-    // Perhaps we should just parse it manually? But perhaps not
-    while let Some(Ok(info)) = stream.next().await {
-        let addr = info.remote_addr().unwrap();
-        let xml = info.text().await?;
-        let mut reader = Reader::from_str(xml.as_str());
-
-        loop {
-            match reader.read_event() {
-                Ok(Event::Start(e)) if e.name().as_ref() == b"friendly-device-name" => {
-                    let name = reader
-                        .read_text(e.name())
-                        .expect("Cannot decode text value")
-                        // Fix the `"` char. There's probably other html chars that need fixing!
-                        .replace("&quot;", "\"");
-
-                    devices.push(RokuDevice {
-                        name: name.to_string(),
-                        addr,
-                    });
-                    break;
-                }
-                Ok(Event::Eof) => break,
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                _ => (),
+    while let Some(result) = stream.next().await {
+        let (body, addr) = match result {
+            Ok(info) => info,
+            Err(e) => {
+                error!(%e, "Failed to query a discovered device");
+                continue;
             }
-        }
+        };
+        let info: DeviceInfo = match decode(&body) {
+            Ok(info) => info,
+            Err(e) => {
+                error!(%e, "Failed to decode a discovered device's info");
+                continue;
+            }
+        };
+
+        devices.push(RokuDevice { name: info.friendly_name, addr });
     }
 
     Ok(devices)
@@ -314,10 +640,43 @@ async fn get_roku_addr() -> Result<Vec<Url>> {
     Ok(urls)
 }
 
-/// Sends a `RokuCommand` to a provided `Url`.
-async fn send_cmd(command: RokuCommand, url: &Url, method: Method) -> Result<Response> {
-    let url = urlify(url, &command)?;
-    debug!(?url, "Sending {:?}", &command);
+/// Sends `RokuCommand::DeviceInfo` to `url` and returns the response body along with
+/// the socket address of the peer that actually answered it.
+///
+/// Used by `get_roku_devices` instead of `send_cmd`, since it needs the responding
+/// peer's address rather than re-resolving the discovery URL's host, which could
+/// disagree with the peer if that host were a hostname with multiple records.
+async fn send_device_info(url: &Url) -> Result<(String, SocketAddr)> {
+    let path = RokuCommand::DeviceInfo.path()?;
+    let full_url = url.join(&path)?;
+
+    let client = Client::new();
+    let resp = client.get(full_url).send().await?;
+    let addr = resp
+        .remote_addr()
+        .ok_or(anyhow!("Could not resolve device address"))?;
+    if !resp.status().is_success() {
+        error!(?resp, "Request to Roku device failed");
+    }
+
+    Ok((resp.text().await?, addr))
+}
+
+/// Sends a `RokuCommand` to a provided `Url` and returns the response body.
+async fn send_cmd(command: RokuCommand, url: &Url, method: Method) -> Result<String> {
+    let path = command.path()?;
+    debug!(?command, %path, "Sending");
+
+    send_to_path(url, &path, method).await
+}
+
+/// Sends a request for an already-resolved ECP path and returns the response body.
+///
+/// Used directly by `Launch`, whose path depends on a device's app catalog rather
+/// than being derivable from the command alone via [`RokuCommand::path`].
+async fn send_to_path(base: &Url, path: &str, method: Method) -> Result<String> {
+    let url = base.join(path)?;
+    debug!(?url, "Sending");
 
     let client = Client::new();
     let resp = client.request(method, url).send().await?;
@@ -325,5 +684,149 @@ async fn send_cmd(command: RokuCommand, url: &Url, method: Method) -> Result<Res
         error!(?resp, "Request to Roku device failed");
     }
 
-    Ok(resp)
+    Ok(resp.text().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn youtube_resolves_video_id_from_query() {
+        let link = Url::parse("https://www.youtube.com/watch?v=abc123").unwrap();
+        let path = YouTube.resolve(&link).unwrap();
+        assert_eq!(path.content_id.as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn youtube_errors_without_video_id() {
+        let link = Url::parse("https://www.youtube.com/watch").unwrap();
+        assert!(YouTube.resolve(&link).is_err());
+    }
+
+    #[test]
+    fn netflix_resolves_title_id_and_defaults_to_movie() {
+        let link = Url::parse("https://www.netflix.com/title/80100172").unwrap();
+        let path = Netflix.resolve(&link).unwrap();
+        assert_eq!(path.content_id.as_deref(), Some("80100172"));
+        assert_eq!(path.media_type.as_deref(), Some("movie"));
+    }
+
+    #[test]
+    fn netflix_detects_series_in_path() {
+        let link = Url::parse("https://www.netflix.com/series/80100172").unwrap();
+        let path = Netflix.resolve(&link).unwrap();
+        assert_eq!(path.media_type.as_deref(), Some("series"));
+    }
+
+    #[test]
+    fn disney_plus_matches_on_app_name_substring() {
+        assert!(DisneyPlus.matches("Disney+"));
+        assert!(!DisneyPlus.matches("Netflix"));
+    }
+
+    #[test]
+    fn disney_plus_resolves_content_id_from_path() {
+        let link = Url::parse("https://www.disneyplus.com/video/abc-123").unwrap();
+        let path = DisneyPlus.resolve(&link).unwrap();
+        assert_eq!(path.content_id.as_deref(), Some("abc-123"));
+    }
+
+    #[test]
+    fn prime_video_prefers_gti_query_param() {
+        let link = Url::parse("https://www.amazon.com/gp/video/detail/xyz?gti=amzn1.dv.gti.abc").unwrap();
+        let path = PrimeVideo.resolve(&link).unwrap();
+        assert_eq!(path.content_id.as_deref(), Some("amzn1.dv.gti.abc"));
+    }
+
+    #[test]
+    fn prime_video_falls_back_to_path_segment() {
+        let link = Url::parse("https://www.amazon.com/gp/video/detail/xyz").unwrap();
+        let path = PrimeVideo.resolve(&link).unwrap();
+        assert_eq!(path.content_id.as_deref(), Some("xyz"));
+    }
+
+    #[test]
+    fn prime_video_matches_name_variants() {
+        assert!(PrimeVideo.matches("Amazon Prime Video"));
+        assert!(PrimeVideo.matches("Prime Video"));
+        assert!(!PrimeVideo.matches("Disney+"));
+    }
+
+    #[test]
+    fn percent_encode_byte_leaves_unreserved_bytes_as_is() {
+        assert_eq!(percent_encode_byte(b'a'), "a");
+        assert_eq!(percent_encode_byte(b'-'), "-");
+        assert_eq!(percent_encode_byte(b'_'), "_");
+        assert_eq!(percent_encode_byte(b'.'), ".");
+        assert_eq!(percent_encode_byte(b'~'), "~");
+    }
+
+    #[test]
+    fn percent_encode_byte_escapes_everything_else() {
+        assert_eq!(percent_encode_byte(b'/'), "%2F");
+        assert_eq!(percent_encode_byte(b' '), "%20");
+    }
+
+    #[test]
+    fn percent_encode_segment_blocks_path_traversal() {
+        let encoded = percent_encode_segment("../query/device-info");
+        assert_eq!(encoded, "..%2Fquery%2Fdevice-info");
+        assert!(!encoded.contains('/'));
+    }
+
+    fn saved_device(alias: &str) -> SavedDevice {
+        SavedDevice {
+            alias: alias.to_string(),
+            device: RokuDevice {
+                name: alias.to_string(),
+                addr: "127.0.0.1:8060".parse().unwrap(),
+            },
+            apps: vec![],
+        }
+    }
+
+    #[test]
+    fn resolve_prefers_explicit_selector_over_default() {
+        let config = Config {
+            default: Some("living-room".to_string()),
+            devices: vec![saved_device("living-room"), saved_device("bedroom")],
+        };
+
+        let resolved = config.resolve(Some("bedroom")).unwrap();
+        assert_eq!(resolved.alias, "bedroom");
+    }
+
+    #[test]
+    fn resolve_falls_back_to_default() {
+        let config = Config {
+            default: Some("bedroom".to_string()),
+            devices: vec![saved_device("living-room"), saved_device("bedroom")],
+        };
+
+        let resolved = config.resolve(None).unwrap();
+        assert_eq!(resolved.alias, "bedroom");
+    }
+
+    #[test]
+    fn resolve_falls_back_to_only_device_with_no_default() {
+        let config = Config { default: None, devices: vec![saved_device("living-room")] };
+
+        let resolved = config.resolve(None).unwrap();
+        assert_eq!(resolved.alias, "living-room");
+    }
+
+    #[test]
+    fn resolve_errors_on_unknown_alias() {
+        let config = Config { default: None, devices: vec![saved_device("living-room")] };
+
+        assert!(config.resolve(Some("garage")).is_err());
+    }
+
+    #[test]
+    fn resolve_errors_with_no_devices_configured() {
+        let config = Config::default();
+
+        assert!(config.resolve(None).is_err());
+    }
 }
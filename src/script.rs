@@ -0,0 +1,132 @@
+//! A small interpreter for scripted command sequences (`joku run <script.toml>`).
+//!
+//! A script is an ordered list of steps run against a device: a [`RokuCommand`], a
+//! `sleep`, or a `wait_until` predicate over the polled [`DeviceState`]. Steps run
+//! sequentially, the whole sequence can repeat, and any failed step or timed-out
+//! wait aborts the run with a clear error.
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use basic_toml::from_str as toml_from_str;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use structopt::StructOpt;
+use tokio::time::{sleep, timeout};
+use tracing::debug;
+
+use crate::{roku::RokuClient, watch};
+
+/// The `DeviceState` fields a `wait_until` predicate may reference.
+const WAIT_FIELDS: [&str; 6] =
+    ["app_id", "app_name", "playback", "position", "duration", "power_mode"];
+
+/// How often to re-poll the device while evaluating a `wait_until` predicate.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The `run` subcommand arguments.
+#[derive(Debug, StructOpt, serde::Serialize, Deserialize, Clone)]
+pub struct RunParams {
+    /// Path to the TOML script to execute.
+    script: PathBuf,
+}
+
+/// A parsed script: an ordered list of steps and an optional repeat count.
+#[derive(Debug, Deserialize)]
+struct Script {
+    #[serde(default)]
+    repeat: Option<u32>,
+    #[serde(default)]
+    steps: Vec<Step>,
+}
+
+/// One step in a script; the present key selects the variant.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Step {
+    /// Sleep for the given number of milliseconds.
+    Sleep { sleep: u64 },
+    /// Block until the device reaches a state, or time out.
+    WaitUntil { wait_until: WaitCondition },
+    /// Send a command to the device.
+    Command { command: crate::roku::RokuCommand },
+}
+
+/// A predicate over a [`DeviceState`] field with a timeout.
+#[derive(Debug, Deserialize)]
+struct WaitCondition {
+    /// The `DeviceState` field to inspect (e.g. `app_name`, `playback`).
+    field: String,
+    /// The value the field must equal for the wait to succeed.
+    equals: String,
+    /// How long to wait before giving up, in milliseconds.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    30_000
+}
+
+/// Runs the script at `params.script` against the device, repeating as configured.
+pub async fn run(params: RunParams, client: RokuClient) -> Result<()> {
+    let script: Script = toml_from_str(&std::fs::read_to_string(&params.script)?)?;
+    let http = Client::new();
+    let base = client.base().clone();
+
+    let repeat = script.repeat.unwrap_or(1);
+    for iteration in 0..repeat {
+        for (index, step) in script.steps.iter().enumerate() {
+            execute(step, &client, &http, &base)
+                .await
+                .with_context(|| format!("step {} failed (iteration {})", index + 1, iteration + 1))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Executes a single step.
+async fn execute(step: &Step, client: &RokuClient, http: &Client, base: &Url) -> Result<()> {
+    match step {
+        Step::Command { command } => {
+            client.send(command.clone(), command.method()).await?;
+        }
+        Step::Sleep { sleep: ms } => sleep(Duration::from_millis(*ms)).await,
+        Step::WaitUntil { wait_until } => wait_until(wait_until, http, base).await?,
+    }
+
+    Ok(())
+}
+
+/// Polls the device until `cond` holds or the timeout elapses.
+async fn wait_until(cond: &WaitCondition, http: &Client, base: &Url) -> Result<()> {
+    if !WAIT_FIELDS.contains(&cond.field.as_str()) {
+        bail!("unknown wait_until field {:?}; expected one of {:?}", cond.field, WAIT_FIELDS);
+    }
+
+    let poll = async {
+        loop {
+            // A transient poll error shouldn't abort the wait; retry until the timeout,
+            // mirroring how `watch` treats outages as recoverable.
+            match watch::poll(http, base).await {
+                Ok(state) if state.get(&cond.field).as_deref() == Some(cond.equals.as_str()) => {
+                    return anyhow::Ok(());
+                }
+                Ok(_) => {}
+                Err(e) => debug!(%e, "poll failed while waiting"),
+            }
+            sleep(WAIT_POLL_INTERVAL).await;
+        }
+    };
+
+    match timeout(Duration::from_millis(cond.timeout), poll).await {
+        Ok(result) => result,
+        Err(_) => bail!(
+            "timed out after {}ms waiting for {} == {}",
+            cond.timeout,
+            cond.field,
+            cond.equals
+        ),
+    }
+}
@@ -0,0 +1,258 @@
+//! A polling-based device-state subscription (`joku watch`).
+//!
+//! Roku's ECP has no push channel, so we emulate a subscribe-and-emit model by
+//! polling `query/active-app`, `query/media-player`, and `query/device-info` on a
+//! fixed interval, diffing each snapshot against the last, and emitting the changed
+//! fields as newline-delimited JSON.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use reqwest::{Client, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_xml_rs::from_str;
+use structopt::StructOpt;
+use tokio::{process::Command, time};
+use tracing::debug;
+
+use crate::roku::RokuClient;
+
+/// Options controlling the polling loop.
+#[derive(Debug, StructOpt, Serialize, Deserialize, Clone)]
+pub struct WatchParams {
+    /// Polling interval in milliseconds.
+    #[structopt(long, default_value = "1000")]
+    interval: u64,
+    /// Shell command run on every emitted event (changes and disconnects); the event
+    /// JSON is passed as `$JOKU_EVENT`.
+    #[structopt(long)]
+    on_change: Option<String>,
+}
+
+/// A snapshot of the fields joku tracks across polls.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceState {
+    app_id: Option<String>,
+    app_name: Option<String>,
+    playback: Option<String>,
+    position: Option<String>,
+    duration: Option<String>,
+    power_mode: Option<String>,
+}
+
+impl DeviceState {
+    /// Returns the value of a tracked field by name, for predicate checks.
+    pub fn get(&self, field: &str) -> Option<String> {
+        match field {
+            "app_id" => self.app_id.clone(),
+            "app_name" => self.app_name.clone(),
+            "playback" => self.playback.clone(),
+            "position" => self.position.clone(),
+            "duration" => self.duration.clone(),
+            "power_mode" => self.power_mode.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// A single field transition observed between two snapshots.
+#[derive(Debug, Serialize)]
+pub struct StateChange {
+    field: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+/// The event emitted to stdout on each tick that produced output.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WatchEvent {
+    /// One or more fields changed; all changes from a single poll are batched here.
+    Changed {
+        timestamp: u128,
+        changes: Vec<StateChange>,
+    },
+    /// A poll failed; the loop keeps running and retries on the next tick.
+    Disconnected { timestamp: u128, error: String },
+}
+
+/// Polls the device until interrupted, emitting NDJSON events on every change.
+pub async fn run(params: WatchParams, client: RokuClient) -> Result<()> {
+    let http = Client::new();
+    let base = client.base().clone();
+
+    let mut ticker = time::interval(Duration::from_millis(params.interval));
+    // Start from an empty snapshot so the first poll reports the initial state.
+    let mut last = DeviceState::default();
+    // A `Disconnected` event should fire once per outage, not on every failed tick.
+    let mut disconnected = false;
+
+    loop {
+        ticker.tick().await;
+
+        match poll(&http, &base).await {
+            Ok(next) => {
+                disconnected = false;
+                // Dedupe identical consecutive snapshots so no event fires on no-change.
+                if next == last {
+                    continue;
+                }
+                let changes = diff(&last, &next);
+                last = next;
+                if changes.is_empty() {
+                    continue;
+                }
+                emit(&params, WatchEvent::Changed { timestamp: now_millis(), changes }).await;
+            }
+            Err(e) => {
+                debug!(%e, "poll failed");
+                if !disconnected {
+                    disconnected = true;
+                    emit(
+                        &params,
+                        WatchEvent::Disconnected { timestamp: now_millis(), error: e.to_string() },
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+}
+
+/// Collects the current state from the three queried endpoints.
+pub async fn poll(http: &Client, base: &Url) -> Result<DeviceState> {
+    let active: ActiveApp = fetch(http, base, "query/active-app").await?;
+    let info: DeviceInfoXml = fetch(http, base, "query/device-info").await?;
+    // `query/media-player` responds even when idle, but tolerate it being unavailable.
+    let player = fetch::<MediaPlayer>(http, base, "query/media-player").await.ok();
+
+    Ok(DeviceState {
+        app_id: active.app.as_ref().and_then(|a| a.id.clone()),
+        app_name: active.app.and_then(|a| a.name),
+        playback: player.as_ref().map(|p| p.state.clone()),
+        position: player.as_ref().and_then(|p| p.position.clone()),
+        duration: player.and_then(|p| p.duration),
+        power_mode: info.power_mode,
+    })
+}
+
+/// Fetches a path off the device base and parses its XML body into `T`.
+async fn fetch<T: DeserializeOwned>(http: &Client, base: &Url, path: &str) -> Result<T> {
+    let url = base.join(path)?;
+    let body = http.get(url).send().await?.error_for_status()?.text().await?;
+
+    Ok(from_str(&body)?)
+}
+
+/// Produces one [`StateChange`] per field that differs between two snapshots.
+fn diff(old: &DeviceState, new: &DeviceState) -> Vec<StateChange> {
+    let mut changes = vec![];
+    let mut push = |field, o: &Option<String>, n: &Option<String>| {
+        if o != n {
+            changes.push(StateChange { field, old: o.clone(), new: n.clone() });
+        }
+    };
+
+    push("app_id", &old.app_id, &new.app_id);
+    push("app_name", &old.app_name, &new.app_name);
+    push("playback", &old.playback, &new.playback);
+    push("position", &old.position, &new.position);
+    push("duration", &old.duration, &new.duration);
+    push("power_mode", &old.power_mode, &new.power_mode);
+
+    changes
+}
+
+/// Writes the event as one JSON line and, if configured, runs the `on_change` command.
+async fn emit(params: &WatchParams, event: WatchEvent) {
+    let line = match serde_json::to_string(&event) {
+        Ok(line) => line,
+        Err(e) => {
+            debug!(%e, "failed to serialize event");
+            return;
+        }
+    };
+
+    println!("{line}");
+
+    if let Some(template) = &params.on_change {
+        if let Err(e) = Command::new("sh").arg("-c").arg(template).env("JOKU_EVENT", &line).status().await {
+            debug!(%e, "on_change command failed");
+        }
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+struct ActiveApp {
+    app: Option<ActiveAppInner>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActiveAppInner {
+    id: Option<String>,
+    #[serde(rename = "$value")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaPlayer {
+    state: String,
+    position: Option<String>,
+    duration: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceInfoXml {
+    #[serde(rename = "power-mode")]
+    power_mode: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(app_id: &str, playback: &str) -> DeviceState {
+        DeviceState {
+            app_id: Some(app_id.to_string()),
+            app_name: None,
+            playback: Some(playback.to_string()),
+            position: None,
+            duration: None,
+            power_mode: None,
+        }
+    }
+
+    #[test]
+    fn no_changes_on_identical_snapshots() {
+        let state = state("12", "play");
+        assert!(diff(&state, &state).is_empty());
+    }
+
+    #[test]
+    fn reports_each_changed_field() {
+        let old = state("12", "play");
+        let new = state("34", "pause");
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().any(|c| c.field == "app_id"
+            && c.old.as_deref() == Some("12")
+            && c.new.as_deref() == Some("34")));
+        assert!(changes.iter().any(|c| c.field == "playback"
+            && c.old.as_deref() == Some("play")
+            && c.new.as_deref() == Some("pause")));
+    }
+
+    #[test]
+    fn none_to_some_is_a_change() {
+        let old = DeviceState::default();
+        let new = state("12", "play");
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+    }
+}